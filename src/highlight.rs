@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+use egui::Color32;
+
+/// One highlighted token: the color to draw it in and its literal text.
+pub type Span = (Color32, String);
+
+/// Caps the number of distinct `(lang, content_hash)` entries retained at
+/// once. Without this, every keystroke in a fenced code block mints a new
+/// cache entry for the edited contents and the old one is never reclaimed,
+/// so memory grows with keystrokes typed rather than with the number of
+/// code blocks in the document.
+const MAX_CACHE_ENTRIES: usize = 64;
+
+/// Tokenizes fenced code blocks by language and caches the resulting spans
+/// so a block is only re-highlighted when its language or contents change.
+/// Entries beyond `MAX_CACHE_ENTRIES` are evicted oldest-first.
+#[derive(Default)]
+pub struct CachingHighlighter {
+    cache: HashMap<(String, u64), Vec<Span>>,
+    order: VecDeque<(String, u64)>,
+}
+
+impl CachingHighlighter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the highlighted spans for `text` in `lang`, using the cache
+    /// when available.
+    pub fn highlight(&mut self, lang: Option<&str>, text: &str) -> &[Span] {
+        let lang = lang.unwrap_or("").to_owned();
+        let key = (lang.clone(), hash_text(text));
+        if !self.cache.contains_key(&key) {
+            while self.cache.len() >= MAX_CACHE_ENTRIES {
+                let Some(oldest) = self.order.pop_front() else { break };
+                self.cache.remove(&oldest);
+            }
+            self.cache.insert(key.clone(), tokenize(&lang, text));
+            self.order.push_back(key.clone());
+        }
+        &self.cache[&key]
+    }
+}
+
+fn hash_text(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A lightweight, dependency-free tokenizer good enough to color the common
+/// categories (keywords, strings, comments, numbers) for a handful of
+/// languages. Unknown languages fall back to a single plain span.
+fn tokenize(lang: &str, text: &str) -> Vec<Span> {
+    let keywords: &[&str] = match lang {
+        "rust" | "rs" => &[
+            "fn", "let", "mut", "if", "else", "match", "for", "while", "loop", "return", "struct",
+            "enum", "impl", "trait", "pub", "use", "mod", "self", "Self", "true", "false",
+        ],
+        "js" | "javascript" | "ts" | "typescript" => &[
+            "function", "let", "const", "var", "if", "else", "for", "while", "return", "class",
+            "import", "export", "true", "false", "null",
+        ],
+        "py" | "python" => &[
+            "def", "class", "if", "elif", "else", "for", "while", "return", "import", "from",
+            "True", "False", "None",
+        ],
+        _ => return vec![(Color32::LIGHT_GRAY, text.to_owned())],
+    };
+
+    const KEYWORD: Color32 = Color32::from_rgb(0xc6, 0x92, 0xe8);
+    const STRING: Color32 = Color32::from_rgb(0xce, 0x91, 0x78);
+    const COMMENT: Color32 = Color32::from_rgb(0x6a, 0x99, 0x55);
+    const NUMBER: Color32 = Color32::from_rgb(0xb5, 0xce, 0xa8);
+    const PLAIN: Color32 = Color32::LIGHT_GRAY;
+
+    let mut spans: Vec<Span> = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    let mut plain_start = 0;
+
+    let mut flush_plain = |spans: &mut Vec<Span>, end: usize, plain_start: &mut usize| {
+        if end > *plain_start {
+            spans.push((PLAIN, text[*plain_start..end].to_owned()));
+        }
+        *plain_start = end;
+    };
+
+    while let Some(&(start, ch)) = chars.peek() {
+        if ch == '"' {
+            flush_plain(&mut spans, start, &mut plain_start);
+            let mut end = start + ch.len_utf8();
+            chars.next();
+            while let Some(&(i, c)) = chars.peek() {
+                chars.next();
+                end = i + c.len_utf8();
+                if c == '"' {
+                    break;
+                }
+            }
+            spans.push((STRING, text[start..end].to_owned()));
+            plain_start = end;
+        } else if (lang == "py" || lang == "python") && ch == '#' || ch == '/' && text[start..].starts_with("//") {
+            flush_plain(&mut spans, start, &mut plain_start);
+            let end = text[start..].find('\n').map(|i| start + i).unwrap_or(text.len());
+            spans.push((COMMENT, text[start..end].to_owned()));
+            plain_start = end;
+            while let Some(&(i, _)) = chars.peek() {
+                if i >= end {
+                    break;
+                }
+                chars.next();
+            }
+        } else if ch.is_ascii_digit() {
+            flush_plain(&mut spans, start, &mut plain_start);
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_ascii_digit() || c == '.' {
+                    end = i + c.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            spans.push((NUMBER, text[start..end].to_owned()));
+            plain_start = end;
+        } else if ch.is_alphabetic() || ch == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_alphanumeric() || c == '_' {
+                    end = i + c.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            if keywords.contains(&&text[start..end]) {
+                flush_plain(&mut spans, start, &mut plain_start);
+                spans.push((KEYWORD, text[start..end].to_owned()));
+                plain_start = end;
+            }
+        } else {
+            chars.next();
+        }
+    }
+    flush_plain(&mut spans, text.len(), &mut plain_start);
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight_caches_identical_lookups() {
+        let mut highlighter = CachingHighlighter::new();
+        let a = highlighter.highlight(Some("rust"), "fn main() {}").to_vec();
+        let b = highlighter.highlight(Some("rust"), "fn main() {}").to_vec();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn cache_evicts_oldest_entry_past_the_cap() {
+        let mut highlighter = CachingHighlighter::new();
+        for i in 0..MAX_CACHE_ENTRIES + 1 {
+            highlighter.highlight(Some("rust"), &format!("let x{i} = {i};"));
+        }
+        assert_eq!(highlighter.cache.len(), MAX_CACHE_ENTRIES);
+        assert!(!highlighter.cache.contains_key(&("rust".to_owned(), hash_text("let x0 = 0;"))));
+    }
+}
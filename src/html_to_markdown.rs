@@ -0,0 +1,205 @@
+use html5ever::parse_document;
+use html5ever::tendril::TendrilSink;
+use markup5ever_rcdom::{Handle, NodeData, RcDom};
+
+/// Convert an HTML fragment (as found on the `text/html` clipboard format)
+/// into Markdown, so rich text dragged or pasted from a browser lands as
+/// clean source instead of raw tags.
+pub fn html_to_markdown(html: &str) -> String {
+    let dom = parse_document(RcDom::default(), Default::default())
+        .from_utf8()
+        .read_from(&mut html.as_bytes())
+        .unwrap_or_default();
+
+    let mut out = String::new();
+    walk(&dom.document, &mut out, 0);
+    collapse_blank_lines(&out)
+}
+
+fn walk(handle: &Handle, out: &mut String, list_depth: usize) {
+    match &handle.data {
+        NodeData::Text { contents } => {
+            let collapsed: String = contents.borrow().split_whitespace().collect::<Vec<_>>().join(" ");
+            if !collapsed.is_empty() {
+                out.push_str(&collapsed);
+                out.push(' ');
+            }
+        }
+        NodeData::Element { name, attrs, .. } => {
+            let tag = name.local.as_ref();
+            match tag {
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    let level: usize = tag[1..].parse().unwrap_or(1);
+                    out.push_str(&"#".repeat(level));
+                    out.push(' ');
+                    walk_children(handle, out, list_depth);
+                    out.push_str("\n\n");
+                }
+                "strong" | "b" => {
+                    out.push_str("**");
+                    walk_children(handle, out, list_depth);
+                    out.push_str("**");
+                }
+                "em" | "i" => {
+                    out.push('*');
+                    walk_children(handle, out, list_depth);
+                    out.push('*');
+                }
+                "a" => {
+                    let href = attrs
+                        .borrow()
+                        .iter()
+                        .find(|attr| attr.name.local.as_ref() == "href")
+                        .map(|attr| attr.value.to_string())
+                        .unwrap_or_default();
+                    out.push('[');
+                    walk_children(handle, out, list_depth);
+                    out.push_str("](");
+                    out.push_str(&href);
+                    out.push(')');
+                }
+                "p" => {
+                    walk_children(handle, out, list_depth);
+                    out.push_str("\n\n");
+                }
+                "br" => out.push_str("  \n"),
+                "ul" => {
+                    // A list nested inside an `<li>` follows straight after
+                    // the parent item's text with no separating newline of
+                    // its own; without one here its first entry would land
+                    // on the same source line as the parent item.
+                    if !out.ends_with('\n') {
+                        out.push('\n');
+                    }
+                    walk_list(handle, out, list_depth, false);
+                    out.push('\n');
+                }
+                "ol" => {
+                    if !out.ends_with('\n') {
+                        out.push('\n');
+                    }
+                    walk_list(handle, out, list_depth, true);
+                    out.push('\n');
+                }
+                "pre" => {
+                    out.push_str("```");
+                    out.push_str(&code_lang(handle));
+                    out.push('\n');
+                    let mut code = String::new();
+                    extract_verbatim_text(handle, &mut code);
+                    out.push_str(code.trim_end_matches('\n'));
+                    out.push_str("\n```\n\n");
+                }
+                "blockquote" => {
+                    let mut inner = String::new();
+                    walk_children(handle, &mut inner, list_depth);
+                    for line in inner.trim().lines() {
+                        out.push_str("> ");
+                        out.push_str(line);
+                        out.push('\n');
+                    }
+                    out.push('\n');
+                }
+                "script" | "style" | "head" => {}
+                _ => walk_children(handle, out, list_depth),
+            }
+        }
+        _ => walk_children(handle, out, list_depth),
+    }
+}
+
+fn walk_children(handle: &Handle, out: &mut String, list_depth: usize) {
+    for child in handle.children.borrow().iter() {
+        walk(child, out, list_depth);
+    }
+}
+
+fn walk_list(handle: &Handle, out: &mut String, list_depth: usize, ordered: bool) {
+    let mut index = 1;
+    for child in handle.children.borrow().iter() {
+        if let NodeData::Element { name, .. } = &child.data {
+            if name.local.as_ref() == "li" {
+                out.push_str(&"  ".repeat(list_depth));
+                if ordered {
+                    out.push_str(&format!("{}. ", index));
+                    index += 1;
+                } else {
+                    out.push_str("- ");
+                }
+                let mut item = String::new();
+                walk_children(child, &mut item, list_depth + 1);
+                out.push_str(item.trim());
+                out.push('\n');
+            }
+        }
+    }
+}
+
+/// Collect a node's text content as-is, with none of `walk`'s prose
+/// whitespace collapsing. Used for `<pre>`/`<code>` bodies, where the
+/// original indentation and line breaks are the content, not filler.
+fn extract_verbatim_text(handle: &Handle, out: &mut String) {
+    match &handle.data {
+        NodeData::Text { contents } => out.push_str(&contents.borrow()),
+        _ => {
+            for child in handle.children.borrow().iter() {
+                extract_verbatim_text(child, out);
+            }
+        }
+    }
+}
+
+fn code_lang(pre: &Handle) -> String {
+    for child in pre.children.borrow().iter() {
+        if let NodeData::Element { name, attrs, .. } = &child.data {
+            if name.local.as_ref() == "code" {
+                for attr in attrs.borrow().iter() {
+                    if attr.name.local.as_ref() == "class" {
+                        if let Some(lang) = attr.value.split_whitespace().find_map(|c| c.strip_prefix("language-")) {
+                            return lang.to_owned();
+                        }
+                    }
+                }
+            }
+        }
+    }
+    String::new()
+}
+
+fn collapse_blank_lines(text: &str) -> String {
+    let mut out = String::new();
+    let mut blank_run = 0;
+    for line in text.trim().lines() {
+        if line.trim().is_empty() {
+            blank_run += 1;
+            if blank_run > 1 {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out.trim_end().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pre_preserves_indentation_and_line_breaks() {
+        let html = "<pre><code>fn main() {\n    42\n}</code></pre>";
+        let markdown = html_to_markdown(html);
+        assert!(markdown.contains("fn main() {\n    42\n}"), "got: {markdown:?}");
+    }
+
+    #[test]
+    fn nested_list_items_start_their_own_line() {
+        let html = "<ul><li>Parent<ul><li>Child</li></ul></li></ul>";
+        let markdown = html_to_markdown(html);
+        assert!(markdown.contains("- Parent\n"), "got: {markdown:?}");
+        assert!(markdown.contains("- Child"), "got: {markdown:?}");
+    }
+}
@@ -1,17 +1,42 @@
+mod highlight;
+mod html_to_markdown;
+mod markdown;
+
 use eframe::egui;
 use egui::{ScrollArea, TextEdit, TextStyle};
 use std::fs::{File, OpenOptions};
 use std::io::BufReader;
 use std::path::PathBuf;
 use serde::{Serialize, Deserialize};
-use pulldown_cmark::{Parser, Event, Tag};
+
+use highlight::CachingHighlighter;
+use markdown::ParsedBlock;
+
+/// Approximate height, in points, of one monospace row in the editor. Used
+/// to map the editor's scroll offset to a source line for preview sync.
+const EDITOR_ROW_HEIGHT: f32 = 18.0;
 
 #[derive(Serialize, Deserialize)]
 struct MarkdownEditor {
     text: String,
+    /// Where `save_changes`/`load_content` JSON-dump and restore app state.
+    /// Never repointed at a document the user navigates to — see
+    /// `current_document_path` for that.
     file_path: PathBuf,
+    /// The markdown file currently open via internal link navigation, if
+    /// any. Only used to resolve further relative links; `save_changes`
+    /// always writes to `file_path`, not this.
+    current_document_path: Option<PathBuf>,
     scroll_offset: f32,
     show_preview: bool,
+    #[serde(skip)]
+    parsed_text: String,
+    #[serde(skip)]
+    parsed_blocks: Vec<(usize, ParsedBlock)>,
+    #[serde(skip)]
+    highlighter: CachingHighlighter,
+    #[serde(skip)]
+    scroll_restored: bool,
 }
 
 impl Default for MarkdownEditor {
@@ -21,8 +46,13 @@ impl Default for MarkdownEditor {
         Self {
             text: "# Welcome to Markdown Editor\n\nStart typing your markdown here!".to_owned(),
             file_path: desktop_path,
+            current_document_path: None,
             scroll_offset: 0.0,
             show_preview: true,
+            parsed_text: String::new(),
+            parsed_blocks: Vec::new(),
+            highlighter: CachingHighlighter::new(),
+            scroll_restored: false,
         }
     }
 }
@@ -55,83 +85,62 @@ impl MarkdownEditor {
             }
         }
     }
-}
 
-fn highlight_markdown(ui: &mut egui::Ui, text: &str) {
-    let parser = Parser::new(text);
-    let mut color = egui::Color32::WHITE;
-    let mut font_size = 14.0;
-    let mut italics = false;
-    let mut bold = false;
-    let mut list_level = 0;
-    let mut in_item = false;
-
-    for event in parser {
-        match event {
-            Event::Start(Tag::Heading(level, _, _)) => {
-                font_size = 24.0 - (level as u8 as f32 * 2.0);
-                color = egui::Color32::LIGHT_BLUE;
-                in_item = false;
-                ui.end_row();
-            },
-            Event::Start(Tag::Paragraph) => {
-                if !in_item {
-                    font_size = 14.0;
-                    color = egui::Color32::WHITE;
-                }
-            },
-            Event::Start(Tag::List(_)) => {
-                list_level += 1;
-            },
-            Event::End(Tag::List(_)) => {
-                list_level -= 1;
-                in_item = false;
-            },
-            Event::Start(Tag::Item) => {
-                in_item = true;
-            },
-            Event::Text(text) => {
-                let mut rich_text = egui::RichText::new(text.to_string())
-                    .color(color)
-                    .size(font_size);
-            
-                if italics {
-                    rich_text = rich_text.italics();
-                }
-                if bold {
-                    rich_text = rich_text.strong();
-                }
-            
-                if in_item {
-                    ui.horizontal(|ui| {
-                        ui.add_space((list_level - 1) as f32 * 20.0);
-                        ui.label("• ");
-                        ui.label(rich_text);
-                    });
-                } else {
-                    ui.label(rich_text);
-                }
-            },
-            
-            Event::End(Tag::Item) => {
-                in_item = false;
-            },
-            Event::SoftBreak | Event::HardBreak => {
-                ui.end_row();
-            },
-            Event::End(_) => {
-                color = egui::Color32::WHITE;
-                font_size = 14.0;
-                italics = false;
-                bold = false;
-            },
-            _ => {}
+    /// Navigate to a relative `.md` file clicked in the preview: resolve it
+    /// against the currently open document's directory (or, if none is open
+    /// yet, the app-state directory), load its contents, and track it as
+    /// `current_document_path`. This never touches `file_path`, which stays
+    /// fixed as the app-state save target.
+    fn follow_internal_link(&mut self, relative_path: &str) {
+        let base = self.current_document_path.as_ref().unwrap_or(&self.file_path);
+        let target = match base.parent() {
+            Some(parent) => parent.join(relative_path),
+            None => PathBuf::from(relative_path),
+        };
+        if let Ok(contents) = std::fs::read_to_string(&target) {
+            self.text = contents;
+            self.current_document_path = Some(target);
+        }
+    }
+
+    /// Read the system clipboard's `text/html` content, convert it to
+    /// Markdown, and append it to `self.text`, separated from any existing
+    /// content by a blank line so it never glues onto the last line.
+    fn paste_html_as_markdown(&mut self) {
+        let Ok(mut clipboard) = arboard::Clipboard::new() else { return };
+        let Ok(html) = clipboard.get().html() else { return };
+        let markdown = html_to_markdown::html_to_markdown(&html);
+        if !self.text.is_empty() {
+            if !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+            self.text.push('\n');
+        }
+        self.text.push_str(&markdown);
+        self.save_changes();
+    }
+
+    /// Re-parse `self.text` into `self.parsed_blocks` only if it changed
+    /// since the last call, so the preview doesn't re-run the parser every
+    /// frame.
+    fn refresh_parsed_blocks(&mut self) {
+        if self.parsed_text != self.text {
+            self.parsed_blocks = markdown::parse_markdown(&self.text);
+            self.parsed_text = self.text.clone();
         }
     }
 }
 
 impl eframe::App for MarkdownEditor {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.refresh_parsed_blocks();
+        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Paste as Markdown").clicked() {
+                    self.paste_html_as_markdown();
+                }
+            });
+        });
         egui::CentralPanel::default().show(ctx, |ui| {
             let available_size = ui.available_size();
             let half_width = available_size.x / 2.0;
@@ -140,41 +149,75 @@ impl eframe::App for MarkdownEditor {
                 // 左側：エディター
                 ui.vertical(|ui| {
                     ui.set_width(half_width);
-                    ui.horizontal(|ui| {
-                        // 行番号
-                        let line_count = self.text.lines().count().max(1);
-                        ui.vertical(|ui| {
-                            ui.set_width(30.0);
-                            ui.style_mut().spacing.item_spacing.y = 0.0;
-                            for i in 1..=line_count {
-                                ui.label(egui::RichText::new(format!("{:3}", i)).monospace());
+                    let mut editor_scroll = ScrollArea::vertical().id_source("editor_scroll");
+                    if !self.scroll_restored {
+                        editor_scroll = editor_scroll.vertical_scroll_offset(self.scroll_offset);
+                    }
+                    let editor_output = editor_scroll.show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            // 行番号
+                            let line_count = self.text.lines().count().max(1);
+                            ui.vertical(|ui| {
+                                ui.set_width(30.0);
+                                ui.style_mut().spacing.item_spacing.y = 0.0;
+                                for i in 1..=line_count {
+                                    ui.label(egui::RichText::new(format!("{:3}", i)).monospace());
+                                }
+                            });
+
+                            // テキストエディタ
+                            let editor_width = half_width - 40.0;
+                            let response = ui.add_sized(
+                                [editor_width, available_size.y - 20.0],
+                                TextEdit::multiline(&mut self.text)
+                                    .font(TextStyle::Monospace)
+                                    .frame(false)
+                            );
+
+                            if response.changed() {
+                                self.save_changes();
                             }
                         });
-
-                        // テキストエディタ
-                        let editor_width = half_width - 40.0;
-                        let response = ui.add_sized(
-                            [editor_width, available_size.y - 20.0],
-                            TextEdit::multiline(&mut self.text)
-                                .font(TextStyle::Monospace)
-                                .frame(false)
-                        );
-
-                        if response.changed() {
-                            self.save_changes();
-                        }
                     });
+                    self.scroll_offset = editor_output.state.offset.y;
+                    self.scroll_restored = true;
                 });
 
                 // 右側：プレビュー
                 ui.vertical(|ui| {
                     ui.set_width(half_width);
                     ui.label("Preview:");
+                    let target_line = (self.scroll_offset / EDITOR_ROW_HEIGHT).floor().max(0.0) as usize;
+                    let target_index = self
+                        .parsed_blocks
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, (line, _))| *line <= target_line)
+                        .map(|(index, _)| index)
+                        .last();
+                    let mut preview_response = markdown::PreviewResponse::default();
                     ScrollArea::vertical()
                         .id_source("preview_scroll")
                         .show(ui, |ui| {
-                            highlight_markdown(ui, &self.text);
+                            for (index, (_, block)) in self.parsed_blocks.iter().enumerate() {
+                                let block_response =
+                                    ui.scope(|ui| markdown::render_block(ui, block, &mut self.highlighter, &mut preview_response)).response;
+                                if Some(index) == target_index {
+                                    ui.scroll_to_rect(block_response.rect, Some(egui::Align::TOP));
+                                }
+                            }
                         });
+                    let any_toggled = !preview_response.toggled_tasks.is_empty();
+                    for marker_range in preview_response.toggled_tasks {
+                        markdown::toggle_task_marker(&mut self.text, marker_range);
+                    }
+                    if any_toggled {
+                        self.save_changes();
+                    }
+
+                    if let Some(link) = preview_response.navigate_to {
+                        self.follow_internal_link(&link);
+                    }
                 });
             });
         });
@@ -0,0 +1,541 @@
+use std::iter::Peekable;
+use std::ops::Range;
+
+use egui::Color32;
+pub use pulldown_cmark::Alignment;
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, OffsetIter, Options, Parser, Tag};
+
+use crate::highlight::CachingHighlighter;
+
+/// An `Event` paired with its byte range in the source text.
+type Evt<'a> = (Event<'a>, Range<usize>);
+
+/// A single inline run inside a paragraph/heading, carrying its own resolved
+/// style so that nested emphasis/strong/code render correctly regardless of
+/// how deeply they're nested in the source.
+#[derive(Debug, Clone)]
+pub enum Inline {
+    Text { text: String, bold: bool, italic: bool },
+    Code { text: String, bold: bool, italic: bool },
+    LineBreak,
+    Link { dest: String, title: String, inlines: Vec<Inline> },
+}
+
+/// A GitHub-style task list marker (`- [ ]` / `- [x]`) found on a list item,
+/// together with the byte range of the `[ ]`/`[x]` token in the source so it
+/// can be toggled in place.
+#[derive(Debug, Clone)]
+pub struct TaskMarker {
+    pub checked: bool,
+    pub marker_range: Range<usize>,
+}
+
+/// A single list item: its rendered content plus an optional task marker.
+#[derive(Debug, Clone)]
+pub struct ListItem {
+    pub blocks: Vec<ParsedBlock>,
+    pub task: Option<TaskMarker>,
+}
+
+/// An owned block-level node of a parsed markdown document. Containers
+/// (`List`, `BlockQuote`) hold fully parsed child blocks rather than raw
+/// events, so nesting to arbitrary depth falls out for free.
+#[derive(Debug, Clone)]
+pub enum ParsedBlock {
+    Heading { level: u8, inlines: Vec<Inline> },
+    Paragraph { inlines: Vec<Inline> },
+    List { ordered: bool, items: Vec<ListItem> },
+    BlockQuote(Vec<ParsedBlock>),
+    CodeBlock { lang: Option<String>, text: String },
+    Table { alignments: Vec<Alignment>, header: Vec<Vec<Inline>>, rows: Vec<Vec<Vec<Inline>>> },
+}
+
+/// Parse `text` into an owned block tree, pairing each top-level block with
+/// the 0-indexed source line it starts on (used to keep the editor and
+/// preview scroll positions in sync). This should be called only when the
+/// source text changes, not on every UI frame.
+pub fn parse_markdown(text: &str) -> Vec<(usize, ParsedBlock)> {
+    let options = Options::ENABLE_TASKLISTS | Options::ENABLE_TABLES;
+    let events: Peekable<OffsetIter> = Parser::new_ext(text, options).into_offset_iter().peekable();
+    let mut events = events;
+    parse_blocks(&mut events, |_| false)
+        .into_iter()
+        .map(|(offset, block)| (byte_offset_to_line(text, offset), block))
+        .collect()
+}
+
+fn byte_offset_to_line(text: &str, offset: usize) -> usize {
+    text.as_bytes()[..offset.min(text.len())].iter().filter(|&&b| b == b'\n').count()
+}
+
+fn is_block_start(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Start(Tag::Heading(..))
+            | Event::Start(Tag::Paragraph)
+            | Event::Start(Tag::List(_))
+            | Event::Start(Tag::BlockQuote)
+            | Event::Start(Tag::CodeBlock(_))
+            | Event::Start(Tag::Table(_))
+    )
+}
+
+fn parse_blocks<'a, I>(events: &mut Peekable<I>, is_end: impl Fn(&Event<'a>) -> bool) -> Vec<(usize, ParsedBlock)>
+where
+    I: Iterator<Item = Evt<'a>>,
+{
+    let mut blocks = Vec::new();
+    loop {
+        let start = events.peek().map(|(_, range)| range.start);
+        match events.peek().map(|(e, _)| e) {
+            None => break,
+            Some(event) if is_end(event) => break,
+            Some(Event::Start(Tag::Heading(..))) => {
+                let level = match events.next() {
+                    Some((Event::Start(Tag::Heading(level, _, _)), _)) => heading_level_to_u8(level),
+                    _ => unreachable!(),
+                };
+                let inlines = parse_inlines(events, |e| matches!(e, Event::End(Tag::Heading(..))));
+                events.next();
+                blocks.push((start.unwrap_or(0), ParsedBlock::Heading { level, inlines }));
+            }
+            Some(Event::Start(Tag::Paragraph)) => {
+                events.next();
+                let inlines = parse_inlines(events, |e| matches!(e, Event::End(Tag::Paragraph)));
+                events.next();
+                blocks.push((start.unwrap_or(0), ParsedBlock::Paragraph { inlines }));
+            }
+            Some(Event::Start(Tag::List(first_number))) => {
+                let ordered = first_number.is_some();
+                events.next();
+                let items = parse_list_items(events);
+                blocks.push((start.unwrap_or(0), ParsedBlock::List { ordered, items }));
+            }
+            Some(Event::Start(Tag::BlockQuote)) => {
+                events.next();
+                let inner = parse_blocks(events, |e| matches!(e, Event::End(Tag::BlockQuote)));
+                events.next();
+                let inner = inner.into_iter().map(|(_, block)| block).collect();
+                blocks.push((start.unwrap_or(0), ParsedBlock::BlockQuote(inner)));
+            }
+            Some(Event::Start(Tag::CodeBlock(_))) => {
+                let kind = match events.next() {
+                    Some((Event::Start(Tag::CodeBlock(kind)), _)) => kind,
+                    _ => unreachable!(),
+                };
+                let (lang, text) = parse_code_block(events, kind);
+                blocks.push((start.unwrap_or(0), ParsedBlock::CodeBlock { lang, text }));
+            }
+            Some(Event::Start(Tag::Table(_))) => {
+                let alignments = match events.next() {
+                    Some((Event::Start(Tag::Table(alignments)), _)) => alignments,
+                    _ => unreachable!(),
+                };
+                blocks.push((start.unwrap_or(0), parse_table(events, alignments)));
+            }
+            _ => {
+                // Tight list items carry their inline content directly,
+                // without a `Paragraph` wrapper. Collect the run as its own
+                // paragraph-equivalent block. `parse_inlines` always
+                // consumes at least the peeked event itself (the catch-all
+                // guarantees it isn't a stop condition), so a standalone
+                // non-inline event like `Event::Rule` or `Event::Html` is
+                // fully handled by that call alone — don't also advance
+                // `events` here, or the next block's `Start` tag gets eaten.
+                let inlines = parse_inlines(events, |e| is_end(e) || is_block_start(e));
+                if !inlines.is_empty() {
+                    blocks.push((start.unwrap_or(0), ParsedBlock::Paragraph { inlines }));
+                }
+            }
+        }
+    }
+    blocks
+}
+
+fn parse_list_items<'a, I>(events: &mut Peekable<I>) -> Vec<ListItem>
+where
+    I: Iterator<Item = Evt<'a>>,
+{
+    let mut items = Vec::new();
+    loop {
+        match events.peek().map(|(e, _)| e) {
+            Some(Event::Start(Tag::Item)) => {
+                events.next();
+                let task = match events.peek() {
+                    Some((Event::TaskListMarker(checked), range)) => {
+                        let marker = TaskMarker { checked: *checked, marker_range: range.clone() };
+                        events.next();
+                        Some(marker)
+                    }
+                    _ => None,
+                };
+                let blocks = parse_blocks(events, |e| matches!(e, Event::End(Tag::Item)))
+                    .into_iter()
+                    .map(|(_, block)| block)
+                    .collect();
+                events.next();
+                items.push(ListItem { blocks, task });
+            }
+            Some(Event::End(Tag::List(_))) => {
+                events.next();
+                break;
+            }
+            None => break,
+            _ => {
+                events.next();
+            }
+        }
+    }
+    items
+}
+
+fn parse_code_block<'a, I>(events: &mut Peekable<I>, kind: CodeBlockKind) -> (Option<String>, String)
+where
+    I: Iterator<Item = Evt<'a>>,
+{
+    let lang = match kind {
+        CodeBlockKind::Fenced(info) => info
+            .split_whitespace()
+            .next()
+            .map(|s| s.to_owned())
+            .filter(|s| !s.is_empty()),
+        CodeBlockKind::Indented => None,
+    };
+
+    let mut text = String::new();
+    for (event, _) in events.by_ref() {
+        match event {
+            Event::Text(t) => text.push_str(&t),
+            Event::End(Tag::CodeBlock(_)) => break,
+            _ => {}
+        }
+    }
+    (lang, text)
+}
+
+fn parse_table<'a, I>(events: &mut Peekable<I>, alignments: Vec<Alignment>) -> ParsedBlock
+where
+    I: Iterator<Item = Evt<'a>>,
+{
+    let mut header = Vec::new();
+    let mut rows = Vec::new();
+    loop {
+        match events.peek().map(|(e, _)| e) {
+            Some(Event::Start(Tag::TableHead)) => {
+                events.next();
+                header = parse_table_row(events, |e| matches!(e, Event::End(Tag::TableHead)));
+            }
+            Some(Event::Start(Tag::TableRow)) => {
+                events.next();
+                rows.push(parse_table_row(events, |e| matches!(e, Event::End(Tag::TableRow))));
+            }
+            Some(Event::End(Tag::Table(_))) => {
+                events.next();
+                break;
+            }
+            None => break,
+            _ => {
+                events.next();
+            }
+        }
+    }
+    ParsedBlock::Table { alignments, header, rows }
+}
+
+fn parse_table_row<'a, I>(events: &mut Peekable<I>, is_row_end: impl Fn(&Event<'a>) -> bool) -> Vec<Vec<Inline>>
+where
+    I: Iterator<Item = Evt<'a>>,
+{
+    let mut cells = Vec::new();
+    loop {
+        match events.peek().map(|(e, _)| e) {
+            Some(event) if is_row_end(event) => {
+                events.next();
+                break;
+            }
+            Some(Event::Start(Tag::TableCell)) => {
+                events.next();
+                let inlines = parse_inlines(events, |e| matches!(e, Event::End(Tag::TableCell)));
+                events.next();
+                cells.push(inlines);
+            }
+            None => break,
+            _ => {
+                events.next();
+            }
+        }
+    }
+    cells
+}
+
+fn parse_inlines<'a, I>(events: &mut Peekable<I>, is_stop: impl Fn(&Event<'a>) -> bool) -> Vec<Inline>
+where
+    I: Iterator<Item = Evt<'a>>,
+{
+    let mut inlines = Vec::new();
+    let mut style_stack = vec![(false, false)]; // (bold, italic)
+
+    while let Some((event, _)) = events.peek() {
+        if is_stop(event) {
+            break;
+        }
+        let (event, _) = events.next().unwrap();
+        match event {
+            Event::Start(Tag::Strong) => {
+                let (_, italic) = *style_stack.last().unwrap();
+                style_stack.push((true, italic));
+            }
+            Event::End(Tag::Strong) => {
+                style_stack.pop();
+            }
+            Event::Start(Tag::Emphasis) => {
+                let (bold, _) = *style_stack.last().unwrap();
+                style_stack.push((bold, true));
+            }
+            Event::End(Tag::Emphasis) => {
+                style_stack.pop();
+            }
+            Event::Text(text) => {
+                let (bold, italic) = *style_stack.last().unwrap();
+                inlines.push(Inline::Text { text: text.to_string(), bold, italic });
+            }
+            Event::Code(text) => {
+                let (bold, italic) = *style_stack.last().unwrap();
+                inlines.push(Inline::Code { text: text.to_string(), bold, italic });
+            }
+            Event::SoftBreak | Event::HardBreak => inlines.push(Inline::LineBreak),
+            Event::Start(Tag::Link(_, dest, title)) => {
+                let dest = dest.to_string();
+                let title = title.to_string();
+                let inner = parse_inlines(events, |e| matches!(e, Event::End(Tag::Link(..))));
+                events.next();
+                inlines.push(Inline::Link { dest, title, inlines: inner });
+            }
+            _ => {}
+        }
+    }
+    inlines
+}
+
+fn heading_level_to_u8(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+/// The result of rendering the preview for one frame: task markers the
+/// user toggled (identified by their source byte range, so the caller can
+/// rewrite `self.text` unambiguously) and any internal `.md` link the user
+/// followed (a path relative to the current file).
+#[derive(Default)]
+pub struct PreviewResponse {
+    pub toggled_tasks: Vec<Range<usize>>,
+    pub navigate_to: Option<String>,
+}
+
+/// Draw a single block (and, recursively, its children) into `ui`.
+pub fn render_block(ui: &mut egui::Ui, block: &ParsedBlock, highlighter: &mut CachingHighlighter, response: &mut PreviewResponse) {
+    render_block_at(ui, block, 0, highlighter, response);
+}
+
+fn render_block_at(
+    ui: &mut egui::Ui,
+    block: &ParsedBlock,
+    depth: usize,
+    highlighter: &mut CachingHighlighter,
+    response: &mut PreviewResponse,
+) {
+    match block {
+        ParsedBlock::Heading { level, inlines } => {
+            let font_size = 24.0 - (*level as f32 * 2.0);
+            ui.horizontal_wrapped(|ui| {
+                render_inlines(ui, inlines, font_size, Color32::LIGHT_BLUE, false, response);
+            });
+        }
+        ParsedBlock::Paragraph { inlines } => {
+            ui.horizontal_wrapped(|ui| {
+                render_inlines(ui, inlines, 14.0, Color32::WHITE, false, response);
+            });
+        }
+        ParsedBlock::List { ordered, items } => {
+            for (index, item) in items.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.add_space(depth as f32 * 20.0);
+                    if let Some(task) = &item.task {
+                        let mut checked = task.checked;
+                        if ui.checkbox(&mut checked, "").changed() {
+                            response.toggled_tasks.push(task.marker_range.clone());
+                        }
+                    } else {
+                        let marker = if *ordered { format!("{}. ", index + 1) } else { "• ".to_owned() };
+                        ui.label(marker);
+                    }
+                    ui.vertical(|ui| {
+                        for block in &item.blocks {
+                            render_block_at(ui, block, depth + 1, highlighter, response);
+                        }
+                    });
+                });
+            }
+        }
+        ParsedBlock::BlockQuote(blocks) => {
+            ui.horizontal(|ui| {
+                ui.add_space(depth as f32 * 20.0);
+                ui.label(egui::RichText::new("┃").color(Color32::GRAY));
+                ui.vertical(|ui| {
+                    for block in blocks {
+                        render_block_at(ui, block, depth + 1, highlighter, response);
+                    }
+                });
+            });
+        }
+        ParsedBlock::CodeBlock { lang, text } => {
+            egui::Frame::group(ui.style()).show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                let spans = highlighter.highlight(lang.as_deref(), text);
+                ui.horizontal_wrapped(|ui| {
+                    for (color, token) in spans {
+                        ui.label(egui::RichText::new(token).monospace().color(*color));
+                    }
+                });
+            });
+        }
+        ParsedBlock::Table { alignments, header, rows } => {
+            egui::Grid::new(ui.next_auto_id())
+                .striped(true)
+                .show(ui, |ui| {
+                    for (col, cell) in header.iter().enumerate() {
+                        render_table_cell(ui, cell, alignments.get(col).copied().unwrap_or(Alignment::None), true, response);
+                    }
+                    ui.end_row();
+                    for row in rows {
+                        for (col, cell) in row.iter().enumerate() {
+                            render_table_cell(ui, cell, alignments.get(col).copied().unwrap_or(Alignment::None), false, response);
+                        }
+                        ui.end_row();
+                    }
+                });
+        }
+    }
+}
+
+fn render_table_cell(ui: &mut egui::Ui, inlines: &[Inline], alignment: Alignment, is_header: bool, response: &mut PreviewResponse) {
+    let layout = match alignment {
+        Alignment::Center => egui::Layout::top_down(egui::Align::Center),
+        Alignment::Right => egui::Layout::top_down(egui::Align::Max),
+        Alignment::Left | Alignment::None => egui::Layout::top_down(egui::Align::Min),
+    };
+    ui.with_layout(layout, |ui| {
+        ui.horizontal_wrapped(|ui| {
+            render_inlines(ui, inlines, 14.0, Color32::WHITE, is_header, response);
+        });
+    });
+}
+
+fn render_inlines(
+    ui: &mut egui::Ui,
+    inlines: &[Inline],
+    font_size: f32,
+    color: Color32,
+    force_bold: bool,
+    response: &mut PreviewResponse,
+) {
+    for inline in inlines {
+        match inline {
+            Inline::Text { text, bold, italic } => {
+                let mut rich_text = egui::RichText::new(text).color(color).size(font_size);
+                if *bold || force_bold {
+                    rich_text = rich_text.strong();
+                }
+                if *italic {
+                    rich_text = rich_text.italics();
+                }
+                ui.label(rich_text);
+            }
+            Inline::Code { text, bold, italic } => {
+                let mut rich_text = egui::RichText::new(text).monospace().background_color(Color32::from_gray(40));
+                if *bold || force_bold {
+                    rich_text = rich_text.strong();
+                }
+                if *italic {
+                    rich_text = rich_text.italics();
+                }
+                ui.label(rich_text);
+            }
+            Inline::LineBreak => {
+                ui.end_row();
+            }
+            Inline::Link { dest, title, inlines: inner } => {
+                let text = inline_plain_text(inner);
+                let link_response = ui.link(text);
+                let link_response = if title.is_empty() { link_response } else { link_response.on_hover_text(title) };
+                if link_response.clicked() {
+                    if dest.starts_with("http://") || dest.starts_with("https://") {
+                        ui.ctx().open_url(egui::OpenUrl::new(dest));
+                    } else if dest.ends_with(".md") {
+                        response.navigate_to = Some(dest.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn inline_plain_text(inlines: &[Inline]) -> String {
+    let mut text = String::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text { text: t, .. } => text.push_str(t),
+            Inline::Code { text: t, .. } => text.push_str(t),
+            Inline::LineBreak => text.push(' '),
+            Inline::Link { inlines, .. } => text.push_str(&inline_plain_text(inlines)),
+        }
+    }
+    text
+}
+
+/// Flip the `[ ]`/`[x]` marker at `marker_range` in `text` in place.
+///
+/// `marker_range` was captured against `text` as it looked when the preview
+/// was last parsed, which can be a frame stale by the time this runs (e.g.
+/// an edit and a checkbox click landing in the same frame). Re-check what's
+/// actually at that range before touching it, rather than trusting it's
+/// still the marker we expect.
+pub fn toggle_task_marker(text: &mut String, marker_range: Range<usize>) {
+    let Some(marker) = text.get(marker_range.clone()) else { return };
+    let replacement = match marker {
+        "[ ]" => "[x]",
+        "[x]" | "[X]" => "[ ]",
+        _ => return,
+    };
+    text.replace_range(marker_range, replacement);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thematic_break_does_not_swallow_the_next_block() {
+        let blocks = parse_markdown("para\n\n---\n\n# Heading");
+        assert_eq!(blocks.len(), 2);
+        assert!(matches!(blocks[0].1, ParsedBlock::Paragraph { .. }));
+        match &blocks[1].1 {
+            ParsedBlock::Heading { level, .. } => assert_eq!(*level, 1),
+            other => panic!("expected a heading, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn thematic_break_does_not_swallow_the_next_list() {
+        let blocks = parse_markdown("- a\n\n---\n\n- b");
+        assert_eq!(blocks.len(), 2);
+        assert!(matches!(blocks[0].1, ParsedBlock::List { .. }));
+        assert!(matches!(blocks[1].1, ParsedBlock::List { .. }));
+    }
+}